@@ -0,0 +1,85 @@
+//! Streaming-read plumbing shared by every archive backend: the per-entry
+//! metadata record and the bounded/inflating readers built on top of it.
+use std::cell::RefCell;
+use std::io::{Read, Seek, SeekFrom};
+
+use flate2::read::ZlibDecoder;
+use lz4_flex::frame::FrameDecoder as Lz4Decoder;
+
+/// Helper data struct for storing info related to a file with a BSA
+#[derive(Debug)]
+pub struct FileStruct {
+    /// Size of the file as stored on disk, i.e. the number of bytes making
+    /// up the (possibly deflated) data section for this entry
+    pub compressed_size: usize,
+    /// True size of the file once decompressed. Equal to `compressed_size`
+    /// when the entry is not compressed
+    pub decompressed_size: usize,
+    /// Whether this entry is stored compressed (zlib, or LZ4 frames in
+    /// Skyrim SE archives)
+    pub compressed: bool,
+    /// Offset of the file in bytes from the start of the BSA
+    pub offset: u32,
+    /// Name of the file, including its folder path for formats that nest
+    /// files under named folders
+    pub name: String,
+}
+
+/// Vec of FileStruct type
+pub type FileList = Vec<FileStruct>;
+
+/// A bounded [`Read`] handle over a single entry's byte range within an
+/// already-open archive. Reading past the entry's stored size yields EOF.
+#[derive(Debug)]
+pub struct EntryReader<'a, R> {
+    file: &'a RefCell<R>,
+    remaining: u64,
+}
+
+impl<'a, R: Read + Seek> EntryReader<'a, R> {
+    /// Seek `file` to `offset` and return a reader bounded to `size` bytes
+    /// from there.
+    pub(crate) fn at(file: &'a RefCell<R>, offset: u64, size: u64) -> std::io::Result<Self> {
+        file.borrow_mut().seek(SeekFrom::Start(offset))?;
+        Ok(Self {
+            file,
+            remaining: size,
+        })
+    }
+}
+
+impl<'a, R: Read + Seek> Read for EntryReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+        let max = (buf.len() as u64).min(self.remaining) as usize;
+        let n = self.file.borrow_mut().read(&mut buf[..max])?;
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// A reader over a single entry's contents, transparently decompressing the
+/// data if the entry is stored compressed. TES3 and the Oblivion/Skyrim LE
+/// directory format (`tes4`) both deflate entries; Skyrim SE instead frames
+/// them as LZ4.
+#[derive(Debug)]
+pub enum EntryContents<'a, R> {
+    /// Entry is stored raw; bytes are handed back unmodified
+    Raw(EntryReader<'a, R>),
+    /// Entry is stored deflated; bytes are inflated on the fly
+    Deflate(ZlibDecoder<EntryReader<'a, R>>),
+    /// Entry is stored as an LZ4 frame; bytes are decoded on the fly
+    Lz4(Lz4Decoder<EntryReader<'a, R>>),
+}
+
+impl<'a, R: Read + Seek> Read for EntryContents<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            EntryContents::Raw(r) => r.read(buf),
+            EntryContents::Deflate(r) => r.read(buf),
+            EntryContents::Lz4(r) => r.read(buf),
+        }
+    }
+}
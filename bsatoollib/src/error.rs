@@ -25,6 +25,10 @@ pub enum BsaError {
     Position { expected: u32, actual: u64 },
     #[error("Expected to write {expected} bytes but was {actual}")]
     BytesWritten { expected: u32, actual: usize },
+    #[error("Failed to inflate compressed entry: {0}")]
+    Decompress(String),
+    #[error("Entry name is not valid Windows-1252: {0}")]
+    FilenameEncoding(String),
 }
 
 /// Result type using BsaErrors
@@ -0,0 +1,226 @@
+//! A read-only FUSE filesystem exposing an open archive's entries as
+//! ordinary files, so tools that don't know about BSAs can still browse
+//! and copy individual assets without a full `extract_all`.
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{self, Read};
+use std::time::{Duration, SystemTime};
+
+use fuser::{FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request};
+
+use crate::BSAFile;
+
+const TTL: Duration = Duration::from_secs(1);
+const ROOT_INO: u64 = 1;
+
+#[derive(Debug)]
+enum Node {
+    Dir {
+        children: HashMap<String, u64>,
+    },
+    File {
+        size: u64,
+    },
+}
+
+/// A read-only FUSE filesystem backed by an open [`BSAFile`]. Directories
+/// are synthesized from the `\`-separated entry names; reads seek into the
+/// archive's data section for that entry via the streaming reader.
+#[derive(Debug)]
+pub struct BsaFuse<'a> {
+    bsa: BSAFile<'a>,
+    nodes: HashMap<u64, Node>,
+    // Reverse index from inode to (parent inode, name) so `read` can
+    // reconstruct the `\`-joined archive name for a file inode.
+    parents: HashMap<u64, (u64, String)>,
+    next_ino: u64,
+}
+
+impl<'a> BsaFuse<'a> {
+    /// Build the inode tree for an already-open archive.
+    pub fn new(bsa: BSAFile<'a>) -> Self {
+        let mut fs = Self {
+            bsa,
+            nodes: HashMap::new(),
+            parents: HashMap::new(),
+            next_ino: ROOT_INO + 1,
+        };
+        fs.nodes.insert(
+            ROOT_INO,
+            Node::Dir {
+                children: HashMap::new(),
+            },
+        );
+
+        let entries: Vec<(String, u64)> = fs
+            .bsa
+            .list()
+            .iter()
+            .map(|f| (f.name.clone(), f.decompressed_size as u64))
+            .collect();
+        for (name, size) in entries {
+            fs.insert_path(&name, size);
+        }
+        fs
+    }
+
+    fn insert_path(&mut self, archive_name: &str, size: u64) {
+        let parts: Vec<&str> = archive_name.split('\\').collect();
+        let mut parent = ROOT_INO;
+        for (i, part) in parts.iter().enumerate() {
+            let is_file = i == parts.len() - 1;
+            let existing = match self.nodes.get(&parent) {
+                Some(Node::Dir { children }) => children.get(*part).copied(),
+                _ => None,
+            };
+            let ino = existing.unwrap_or_else(|| {
+                let ino = self.next_ino;
+                self.next_ino += 1;
+                let node = if is_file {
+                    Node::File { size }
+                } else {
+                    Node::Dir {
+                        children: HashMap::new(),
+                    }
+                };
+                self.nodes.insert(ino, node);
+                self.parents.insert(ino, (parent, part.to_string()));
+                if let Some(Node::Dir { children }) = self.nodes.get_mut(&parent) {
+                    children.insert(part.to_string(), ino);
+                }
+                ino
+            });
+            parent = ino;
+        }
+    }
+
+    fn attr_for(&self, ino: u64) -> Option<FileAttr> {
+        let node = self.nodes.get(&ino)?;
+        let now = SystemTime::now();
+        let (kind, size, perm, nlink) = match node {
+            Node::Dir { .. } => (FileType::Directory, 0, 0o555, 2),
+            Node::File { size } => (FileType::RegularFile, *size, 0o444, 1),
+        };
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: (size + 511) / 512,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            crtime: now,
+            kind,
+            perm,
+            nlink,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        })
+    }
+
+    /// Reconstruct the `\`-joined archive name for a file inode.
+    fn full_name(&self, ino: u64) -> Option<String> {
+        let mut parts = Vec::new();
+        let mut current = ino;
+        while current != ROOT_INO {
+            let (parent, name) = self.parents.get(&current)?;
+            parts.push(name.clone());
+            current = *parent;
+        }
+        parts.reverse();
+        Some(parts.join("\\"))
+    }
+}
+
+impl<'a> Filesystem for BsaFuse<'a> {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let name = match name.to_str() {
+            Some(n) => n,
+            None => return reply.error(libc::ENOENT),
+        };
+        let child = match self.nodes.get(&parent) {
+            Some(Node::Dir { children }) => children.get(name).copied(),
+            _ => None,
+        };
+        match child.and_then(|ino| self.attr_for(ino)) {
+            Some(attr) => reply.entry(&TTL, &attr, 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.attr_for(ino) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let children = match self.nodes.get(&ino) {
+            Some(Node::Dir { children }) => children.clone(),
+            _ => return reply.error(libc::ENOENT),
+        };
+
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_string()),
+            (ino, FileType::Directory, "..".to_string()),
+        ];
+        for (name, child_ino) in children {
+            let kind = match self.nodes.get(&child_ino) {
+                Some(Node::Dir { .. }) => FileType::Directory,
+                _ => FileType::RegularFile,
+            };
+            entries.push((child_ino, kind, name));
+        }
+
+        for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            if reply.add(ino, (i + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let name = match self.full_name(ino) {
+            Some(n) => n,
+            None => return reply.error(libc::ENOENT),
+        };
+        let mut reader = match self.bsa.get_file_reader(&name) {
+            Ok(r) => r,
+            Err(_) => return reply.error(libc::EIO),
+        };
+
+        // The underlying entry reader is forward-only (compressed entries
+        // can't seek backward without re-inflating from the start), so a
+        // random-access `read` discards up to `offset` bytes first.
+        if io::copy(&mut (&mut reader).take(offset as u64), &mut io::sink()).is_err() {
+            return reply.error(libc::EIO);
+        }
+
+        let mut buf = Vec::with_capacity(size as usize);
+        match (&mut reader).take(size as u64).read_to_end(&mut buf) {
+            Ok(_) => reply.data(&buf),
+            Err(_) => reply.error(libc::EIO),
+        }
+    }
+}
@@ -0,0 +1,1019 @@
+//! The original Morrowind-era BSA layout: a flat 12-byte header followed by
+//! a size/offset table, a null-separated filename table and a hash table,
+//! identified by the `[0x00, 0x01, 0x00, 0x00]` magic. See [`Tes3Bsa`].
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use encoding_rs::WINDOWS_1252;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::entry::{EntryContents, EntryReader, FileList, FileStruct};
+use crate::error::{BsaError, Result};
+use crate::ArchiveReader;
+
+/// Magic bytes identifying a TES3 (Morrowind) BSA
+pub const MAGIC_HEADER: &[u8] = &[0x0, 0x1, 0x0, 0x0];
+/// Top bit of a stored entry size marking the entry as deflated
+const COMPRESSED_FLAG: u32 = 0x8000_0000;
+
+/// The real Morrowind filename hash: low word over the first half of the
+/// (lowercased) name, high word continuing over the second half with a
+/// rotate. Also reused by [`crate::tes4`] as a stable, internally
+/// consistent stand-in since nothing there validates hashes on read.
+pub(crate) fn calculate_hash(name: &str) -> u64 {
+    let lower_name = name.to_ascii_lowercase();
+    let characters: Vec<char> = lower_name.chars().collect();
+    let len = characters.len() as u32;
+    let l = len >> 1;
+
+    let (mut sum, mut off): (u32, u32) = (0, 0);
+    for c in characters.iter().take(l as usize) {
+        sum ^= (*c as u32) << (off & 0x1F);
+        off += 8;
+    }
+    let low = sum;
+
+    let mut sum: u32 = 0;
+    off = 0;
+    // The second loop picks up where the first left off (index `l`), not
+    // from the start of the name, matching the real Morrowind algorithm.
+    for c in characters.iter().skip(l as usize) {
+        let temp = (*c as u32) << (off & 0x1F);
+        sum ^= temp;
+        let n = temp & 0x1F;
+        sum = sum.rotate_right(n);
+        off += 8;
+    }
+    let high = sum;
+    (low as u64) | ((high as u64) << 32)
+}
+
+fn check_bytes_written(expected: u32, actual: usize) -> Result<()> {
+    if expected != actual as u32 {
+        return Err(BsaError::BytesWritten { expected, actual });
+    }
+    Ok(())
+}
+
+/// Iterator over the entries of an open archive, yielding each entry's
+/// metadata along with a reader bounded to that entry's data.
+#[derive(Debug)]
+pub struct Entries<'a, R> {
+    bsa: &'a Tes3Bsa<'a, R>,
+    index: usize,
+}
+
+impl<'a, R: Read + Seek> Iterator for Entries<'a, R> {
+    type Item = Result<(&'a FileStruct, EntryContents<'a, R>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let fs = self.bsa.files.get(self.index)?;
+        self.index += 1;
+        Some(self.bsa.reader_for(fs).map(|r| (fs, r)))
+    }
+}
+
+/// Category of problem found by [`Tes3Bsa::verify`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// Stored name hash doesn't match the recomputed hash
+    BadHash,
+    /// Entry's data range extends past the end of the archive
+    OutOfBounds,
+    /// Entry's data range overlaps the following entry's, in offset order
+    Overlap,
+    /// Unused bytes sit between this entry's data and the next, in offset
+    /// order
+    Gap,
+}
+
+/// A single problem found while verifying an archive's integrity
+#[derive(Debug)]
+pub struct VerifyIssue {
+    /// Name of the offending entry
+    pub name: String,
+    /// Category of the problem, for callers that want to filter or count
+    /// rather than parse `message`
+    pub kind: IssueKind,
+    /// Human readable description of the problem
+    pub message: String,
+}
+
+/// Report produced by [`Tes3Bsa::verify`]: every integrity problem found
+/// across the archive, if any.
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    /// Individual problems found, one per offending entry/issue pair
+    pub issues: Vec<VerifyIssue>,
+}
+
+impl ScanReport {
+    /// True when no problems were found
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a ScanReport {
+    type Item = &'a VerifyIssue;
+    type IntoIter = std::slice::Iter<'a, VerifyIssue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.issues.iter()
+    }
+}
+
+/// Reader/writer for the Morrowind-era (TES3) BSA layout. Generic over any
+/// `R: Read + Seek` source (a `Cursor<Vec<u8>>`, an mmap'd region, a nested
+/// archive, ...), the same model `tar` and `fuchsia-archive` use. The
+/// path-based [`Tes3Bsa::new`]/[`Tes3Bsa::open`] are thin wrappers around
+/// [`Tes3Bsa::read_from`] for the common filesystem case.
+#[derive(Debug)]
+pub struct Tes3Bsa<'a, R = BufReader<File>> {
+    files: FileList,
+    is_loaded: bool,
+    filename: &'a str,
+    lookup: HashMap<String, u32>,
+    reader: Option<RefCell<R>>,
+    /// Name hashes as stored in the archive's hash table, in directory order
+    stored_hashes: Vec<u64>,
+    /// Total size of the archive in bytes, as of the last `open`
+    archive_len: u64,
+    /// Raw bytes for entries added via [`Tes3Bsa::add_file`], keyed by
+    /// archive name, kept separate from `files` until [`Tes3Bsa::save_as`]
+    /// flushes them to disk alongside the untouched entries
+    staged: HashMap<String, Vec<u8>>,
+}
+
+impl<'a, R> Default for Tes3Bsa<'a, R> {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            is_loaded: false,
+            filename: "",
+            lookup: HashMap::new(),
+            reader: None,
+            stored_hashes: Vec::new(),
+            archive_len: 0,
+            staged: HashMap::new(),
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Tes3Bsa<'a, R> {
+    /// Parse a BSA directory from an arbitrary `Read + Seek` source,
+    /// keeping the reader around so entries can be streamed later. Unlike
+    /// [`Tes3Bsa::open`] this never touches the filesystem, so it works
+    /// equally well with an in-memory `Cursor<Vec<u8>>` or a slice of a
+    /// larger nested archive.
+    pub fn read_from(mut reader: R) -> Result<Self> {
+        let mut obj = Self::default();
+        obj.parse_header(&mut reader)?;
+        obj.reader = Some(RefCell::new(reader));
+        obj.is_loaded = true;
+        Ok(obj)
+    }
+
+    /// Check whether a given file name exists within the BSA
+    pub fn exists(&self, file: &str) -> bool {
+        self.ensure_loaded().is_ok() && self.get_index(file).is_ok()
+    }
+
+    /// Path this archive was opened or created from, as passed to
+    /// [`Tes3Bsa::new`]/[`Tes3Bsa::open`]/[`Tes3Bsa::create`]. Empty for an
+    /// archive built via [`Tes3Bsa::read_from`].
+    pub fn filename(&self) -> &str {
+        self.filename
+    }
+
+    /// Get the file bytes for a given file name within the BSA. Compressed
+    /// entries are transparently inflated.
+    pub fn get_file(&self, file: &str) -> Result<Vec<u8>> {
+        self.ensure_loaded()?;
+        let i = self.get_index(file)?;
+        let fs = &self.files[i as usize];
+
+        let mut reader = self.reader_for(fs)?;
+        let mut buf = Vec::with_capacity(fs.decompressed_size);
+        reader.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Get a streaming reader over a single file's bytes, without buffering
+    /// the whole entry into memory. Compressed entries are inflated as they
+    /// are read.
+    pub fn get_file_reader(&self, file: &str) -> Result<EntryContents<'_, R>> {
+        self.ensure_loaded()?;
+        let i = self.get_index(file)?;
+        self.reader_for(&self.files[i as usize])
+    }
+
+    /// Iterate over every entry in the archive, each paired with a reader
+    /// bounded to that entry's data, in the style of `tar::Archive::entries`.
+    pub fn entries(&self) -> Entries<'_, R> {
+        self.ensure_loaded().unwrap();
+        Entries {
+            bsa: self,
+            index: 0,
+        }
+    }
+
+    /// Call `cb` with every entry's decompressed bytes, in directory order.
+    /// A single buffer is reused across entries, growing to the largest
+    /// entry seen so far instead of allocating a fresh `Vec` per file, to
+    /// keep bulk dumps of archives with thousands of entries from thrashing
+    /// the allocator.
+    pub fn extract_all<F: FnMut(&FileStruct, &[u8])>(&self, mut cb: F) -> Result<()> {
+        self.ensure_loaded()?;
+        let mut buf: Vec<u8> = Vec::new();
+        for fs in &self.files {
+            if fs.decompressed_size > buf.len() {
+                buf.resize(fs.decompressed_size, 0);
+            }
+            let mut reader = self.reader_for(fs)?;
+            reader.read_exact(&mut buf[..fs.decompressed_size])?;
+            cb(fs, &buf[..fs.decompressed_size]);
+        }
+        Ok(())
+    }
+
+    /// Convenience wrapper around [`Tes3Bsa::extract_all`] that writes every
+    /// entry under `out_dir`, recreating the archive's folder structure.
+    pub fn extract_all_to_dir(&self, out_dir: &str) -> Result<()> {
+        let mut result = Ok(());
+        self.extract_all(|fs, data| {
+            if result.is_err() {
+                return;
+            }
+            result = (|| -> Result<()> {
+                let target = Path::new(out_dir).join(fs.name.replace('\\', "/"));
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(target, data)?;
+                Ok(())
+            })();
+        })?;
+        result
+    }
+
+    fn reader_for(&self, fs: &FileStruct) -> Result<EntryContents<'_, R>> {
+        let reader = self.reader.as_ref().ok_or(BsaError::NotOpen)?;
+        let entry = EntryReader::at(reader, fs.offset as u64, fs.compressed_size as u64)?;
+
+        if !fs.compressed {
+            return Ok(EntryContents::Raw(entry));
+        }
+
+        // Compressed entries are prefixed with a 4-byte LE uncompressed
+        // length before the deflate stream itself; skip over it here since
+        // the decompressed size is already known from the header.
+        let mut prefixed = entry;
+        let mut len_buf = [0u8; 4];
+        prefixed.read_exact(&mut len_buf)?;
+        Ok(EntryContents::Deflate(ZlibDecoder::new(prefixed)))
+    }
+
+    /// Get the data for files with the BSA
+    pub fn get_list(&self) -> &FileList {
+        self.ensure_loaded().unwrap();
+        &self.files
+    }
+
+    fn ensure_loaded(&self) -> Result<()> {
+        if !self.is_loaded {
+            return Err(BsaError::NotOpen);
+        }
+        Ok(())
+    }
+
+    fn ensure_not_loaded(&self) -> Result<()> {
+        if self.is_loaded {
+            return Err(BsaError::AlreadyOpen);
+        }
+        Ok(())
+    }
+
+    /// Parse the archive directory from `file`, populating `self.files`,
+    /// `self.lookup` and `self.stored_hashes`. Leaves `file` positioned
+    /// right after the hash table; the caller is responsible for stashing
+    /// it away as `self.reader` once parsing succeeds.
+    fn parse_header(&mut self, file: &mut R) -> Result<()> {
+        self.ensure_not_loaded()?;
+
+        // Total archive size
+        let fsize = file.seek(SeekFrom::End(0))?;
+        file.seek(SeekFrom::Start(0))?;
+        if fsize < 12 {
+            return Err(BsaError::TooSmall(fsize));
+        }
+
+        // Get essential header numbers
+        let dirsize: u32;
+        let filenum: u32;
+        {
+            // First 12 bytes
+            let mut buff = [0u8; 4];
+            file.read_exact(&mut buff)?;
+
+            if buff[..4] != *MAGIC_HEADER {
+                return Err(BsaError::BadHeader);
+            }
+
+            // Total number of bytes used in size/offset-table + filename
+            // sections. AKA hashOffset.
+            file.read_exact(&mut buff)?;
+            dirsize = u32::from_le_bytes(buff);
+
+            // Number of files
+            file.read_exact(&mut buff)?;
+            filenum = u32::from_le_bytes(buff);
+        }
+
+        // Each file must take up at least 21 bytes of data in the bsa. So
+        // if files*21 overflows the file size then we are guaranteed that
+        // the archive is corrupt.
+        if (filenum as u64 * 21 > (fsize - 12))
+            || (dirsize as u64 + 8 * filenum as u64 > (fsize - 12))
+        {
+            return Err(BsaError::DirSize);
+        }
+
+        // Read the offset info into a temporary buffer
+        let mut offsets: Vec<u32> = Vec::with_capacity(filenum as usize);
+        let mut offsets_handle = file.take(12 * filenum as u64);
+        let mut offsets_buffer = Vec::new();
+        offsets_handle.read_to_end(&mut offsets_buffer)?;
+        let mut buff: [u8; 4];
+        for b in (0..12 * filenum as usize).step_by(4) {
+            buff = offsets_buffer[b..b + 4].try_into().unwrap();
+            offsets.push(u32::from_le_bytes(buff));
+        }
+        let mut file = offsets_handle.into_inner();
+
+        // Read the string table. Names are historically stored in
+        // Windows-1252, not UTF-8, so decode through that codec rather than
+        // `String::from_utf8`, which would panic on accented filenames.
+        let mut buff: Vec<u8> = vec![0; dirsize as usize - 12 * filenum as usize];
+        file.read_exact(&mut buff)?;
+        let (string_buf, _, had_errors) = WINDOWS_1252.decode(&buff);
+        if had_errors {
+            return Err(BsaError::FilenameEncoding(string_buf.into_owned()));
+        }
+        let string_vec = string_buf.split('\0').collect::<Vec<&str>>();
+
+        // Check our position
+        if file.stream_position()? != 12 + dirsize as u64 {
+            return Err(BsaError::Position {
+                expected: 12 + dirsize,
+                actual: file.stream_position()?,
+            });
+        }
+
+        // Calculate the offset of the data buffer. All file offsets are
+        // relative to this. 12 header bytes + directory + hash table (skipped)
+        let file_data_offset = 12 + dirsize + 8 * filenum;
+
+        // Set up the the FileStruct table. The top bit of the stored size
+        // marks the entry as compressed, mirroring the convention used by
+        // later Bethesda archive formats.
+        for i in 0..filenum {
+            let raw_size = offsets[i as usize * 2];
+            let compressed = raw_size & COMPRESSED_FLAG != 0;
+            let compressed_size = (raw_size & !COMPRESSED_FLAG) as usize;
+            let offset = offsets[i as usize * 2 + 1] + file_data_offset;
+
+            if offset as u64 + compressed_size as u64 > fsize {
+                return Err(BsaError::OffsetOutside);
+            }
+
+            // Compressed entries carry a 4-byte LE uncompressed-length
+            // prefix ahead of the deflate stream; peek it now so callers
+            // can rely on `decompressed_size` without touching the reader.
+            let decompressed_size = if compressed {
+                file.seek(SeekFrom::Start(offset as u64))?;
+                let mut len_buf = [0u8; 4];
+                file.read_exact(&mut len_buf)?;
+                u32::from_le_bytes(len_buf) as usize
+            } else {
+                compressed_size
+            };
+
+            let fs = FileStruct {
+                compressed_size,
+                decompressed_size,
+                compressed,
+                offset,
+                name: string_vec[i as usize].to_string(),
+            };
+
+            self.lookup.insert(fs.name.to_string(), i);
+            self.files.push(fs);
+        }
+
+        // Read the hash table immediately following the string table, one
+        // u64 per entry in directory order, for later use by `verify`.
+        file.seek(SeekFrom::Start(12 + dirsize as u64))?;
+        let mut hash_buf = [0u8; 8];
+        for _ in 0..filenum {
+            file.read_exact(&mut hash_buf)?;
+            self.stored_hashes.push(u64::from_le_bytes(hash_buf));
+        }
+
+        self.archive_len = fsize;
+
+        Ok(())
+    }
+
+    /// Recompute the name hash of every entry and compare it to the
+    /// archive's stored hash table, flag any entry whose data range falls
+    /// outside the archive, and walk entries in on-disk offset order to
+    /// find regions that overlap or leave gaps of unused space between one
+    /// entry's data and the next. Returns an empty report when the archive
+    /// is consistent; [`Tes3Bsa::repack`] can reclaim any gaps found.
+    pub fn verify(&self) -> Result<ScanReport> {
+        self.ensure_loaded()?;
+        let mut issues = Vec::new();
+
+        for (i, fs) in self.files.iter().enumerate() {
+            let expected = calculate_hash(&fs.name);
+            if let Some(&stored) = self.stored_hashes.get(i) {
+                if stored != expected {
+                    issues.push(VerifyIssue {
+                        name: fs.name.clone(),
+                        kind: IssueKind::BadHash,
+                        message: format!(
+                            "hash mismatch: stored 0x{:016x}, computed 0x{:016x}",
+                            stored, expected
+                        ),
+                    });
+                }
+            }
+
+            if fs.offset as u64 + fs.compressed_size as u64 > self.archive_len {
+                issues.push(VerifyIssue {
+                    name: fs.name.clone(),
+                    kind: IssueKind::OutOfBounds,
+                    message: format!(
+                        "entry offset 0x{:x} + size {} falls outside the archive ({} bytes)",
+                        fs.offset, fs.compressed_size, self.archive_len
+                    ),
+                });
+            }
+        }
+
+        let mut order: Vec<usize> = (0..self.files.len()).collect();
+        order.sort_by_key(|&i| self.files[i].offset);
+        for pair in order.windows(2) {
+            let (cur, next) = (&self.files[pair[0]], &self.files[pair[1]]);
+            let cur_end = cur.offset as u64 + cur.compressed_size as u64;
+            let next_start = next.offset as u64;
+            if cur_end > next_start {
+                issues.push(VerifyIssue {
+                    name: cur.name.clone(),
+                    kind: IssueKind::Overlap,
+                    message: format!(
+                        "entry data overlaps '{}' by {} bytes",
+                        next.name,
+                        cur_end - next_start
+                    ),
+                });
+            } else if cur_end < next_start {
+                issues.push(VerifyIssue {
+                    name: cur.name.clone(),
+                    kind: IssueKind::Gap,
+                    message: format!(
+                        "{} unused bytes between this entry and '{}'",
+                        next_start - cur_end,
+                        next.name
+                    ),
+                });
+            }
+        }
+
+        Ok(ScanReport { issues })
+    }
+
+    /// Rewrite the archive to `out`, laying out every entry's data
+    /// contiguously in directory order, eliminating the gaps (and any
+    /// overlaps) [`Tes3Bsa::verify`] can flag. The directory itself (names,
+    /// hashes, entry order) is left untouched; only each entry's stored
+    /// `offset` changes.
+    pub fn repack(&self, out: &str) -> Result<()> {
+        self.ensure_loaded()?;
+        let reader = self.reader.as_ref().ok_or(BsaError::NotOpen)?;
+
+        // Pull every entry's bytes exactly as stored on disk (already
+        // deflated where applicable) and compute the new, contiguous
+        // offset each one will get.
+        let mut blobs: Vec<Vec<u8>> = Vec::with_capacity(self.files.len());
+        let mut new_offsets: Vec<u32> = Vec::with_capacity(self.files.len());
+        let mut offset = 0u32;
+        for fs in &self.files {
+            reader
+                .borrow_mut()
+                .seek(SeekFrom::Start(fs.offset as u64))?;
+            let mut blob = vec![0u8; fs.compressed_size];
+            reader.borrow_mut().read_exact(&mut blob)?;
+            new_offsets.push(offset);
+            offset += fs.compressed_size as u32;
+            blobs.push(blob);
+        }
+
+        let filenum = self.files.len() as u32;
+        let f = File::create(out)?;
+        let mut f = BufWriter::new(f);
+
+        f.write_all(MAGIC_HEADER)?;
+        let mut hash_offset: u32 = 12 * filenum;
+        for file in &self.files {
+            hash_offset += file.name.chars().count() as u32 + 1;
+        }
+        f.write_all(&hash_offset.to_le_bytes())?;
+        f.write_all(&filenum.to_le_bytes())?;
+
+        for (file, &new_offset) in self.files.iter().zip(&new_offsets) {
+            let mut stored_size = file.compressed_size as u32;
+            if file.compressed {
+                stored_size |= COMPRESSED_FLAG;
+            }
+            f.write_all(&stored_size.to_le_bytes())?;
+            f.write_all(&new_offset.to_le_bytes())?;
+        }
+
+        let mut starting_offset: u32 = 0;
+        for file in &self.files {
+            f.write_all(&starting_offset.to_le_bytes())?;
+            starting_offset += file.name.chars().count() as u32 + 1;
+        }
+
+        let null_term = [b'\0'];
+        for file in &self.files {
+            let (encoded, _, had_errors) = WINDOWS_1252.encode(&file.name);
+            if had_errors {
+                return Err(BsaError::FilenameEncoding(file.name.clone()));
+            }
+            f.write_all(&encoded)?;
+            f.write_all(&null_term)?;
+        }
+
+        for hash in &self.stored_hashes {
+            f.write_all(&hash.to_le_bytes())?;
+        }
+
+        for blob in &blobs {
+            f.write_all(blob)?;
+        }
+        f.flush()?;
+        Ok(())
+    }
+
+    /// Stage a new or replacement entry's raw bytes under `archive_name`,
+    /// overwriting any existing entry of that name. The change only lands on
+    /// disk once [`Tes3Bsa::save_as`] is called, the same append-then-finish
+    /// workflow as `tar::Builder`.
+    pub fn add_file(&mut self, archive_name: &str, data: &[u8]) {
+        self.remove_file(archive_name);
+        let fs = FileStruct {
+            compressed_size: data.len(),
+            decompressed_size: data.len(),
+            compressed: false,
+            offset: 0,
+            name: archive_name.to_string(),
+        };
+        self.lookup.insert(fs.name.clone(), self.files.len() as u32);
+        self.files.push(fs);
+        self.staged.insert(archive_name.to_string(), data.to_vec());
+    }
+
+    /// Remove an entry, if present. A no-op on a name that isn't in the
+    /// archive, mirroring `HashMap::remove`.
+    pub fn remove_file(&mut self, archive_name: &str) {
+        if let Some(index) = self.lookup.remove(archive_name) {
+            self.files.remove(index as usize);
+            self.staged.remove(archive_name);
+            for v in self.lookup.values_mut() {
+                if *v > index {
+                    *v -= 1;
+                }
+            }
+        }
+    }
+
+    /// Flush the current `files`/`staged` state to a new archive at `out`.
+    /// Staged entries are written from their in-memory buffers; untouched
+    /// entries have their raw (possibly deflated) bytes pulled straight from
+    /// the original archive's data region, the same as [`Tes3Bsa::repack`].
+    /// The directory is re-sorted by name hash, as the format requires for
+    /// hash-based lookup, since `add_file`/`remove_file` don't preserve that
+    /// order.
+    pub fn save_as(&self, out: &str) -> Result<()> {
+        let mut blobs: Vec<Vec<u8>> = Vec::with_capacity(self.files.len());
+        for fs in &self.files {
+            if let Some(data) = self.staged.get(&fs.name) {
+                blobs.push(data.clone());
+            } else {
+                let reader = self.reader.as_ref().ok_or(BsaError::NotOpen)?;
+                reader
+                    .borrow_mut()
+                    .seek(SeekFrom::Start(fs.offset as u64))?;
+                let mut blob = vec![0u8; fs.compressed_size];
+                reader.borrow_mut().read_exact(&mut blob)?;
+                blobs.push(blob);
+            }
+        }
+
+        let mut order: Vec<usize> = (0..self.files.len()).collect();
+        order.sort_by_key(|&i| calculate_hash(&self.files[i].name));
+
+        let filenum = self.files.len() as u32;
+        let f = File::create(out)?;
+        let mut f = BufWriter::new(f);
+
+        f.write_all(MAGIC_HEADER)?;
+        let mut hash_offset: u32 = 12 * filenum;
+        for &i in &order {
+            hash_offset += self.files[i].name.chars().count() as u32 + 1;
+        }
+        f.write_all(&hash_offset.to_le_bytes())?;
+        f.write_all(&filenum.to_le_bytes())?;
+
+        let mut data_offset = 0u32;
+        let mut offsets = Vec::with_capacity(order.len());
+        for &i in &order {
+            offsets.push(data_offset);
+            data_offset += blobs[i].len() as u32;
+        }
+        for (&i, &offset) in order.iter().zip(&offsets) {
+            let file = &self.files[i];
+            let mut stored_size = file.compressed_size as u32;
+            if file.compressed {
+                stored_size |= COMPRESSED_FLAG;
+            }
+            f.write_all(&stored_size.to_le_bytes())?;
+            f.write_all(&offset.to_le_bytes())?;
+        }
+
+        let mut starting_offset: u32 = 0;
+        for &i in &order {
+            f.write_all(&starting_offset.to_le_bytes())?;
+            starting_offset += self.files[i].name.chars().count() as u32 + 1;
+        }
+
+        let null_term = [b'\0'];
+        for &i in &order {
+            let (encoded, _, had_errors) = WINDOWS_1252.encode(&self.files[i].name);
+            if had_errors {
+                return Err(BsaError::FilenameEncoding(self.files[i].name.clone()));
+            }
+            f.write_all(&encoded)?;
+            f.write_all(&null_term)?;
+        }
+
+        for &i in &order {
+            f.write_all(&calculate_hash(&self.files[i].name).to_le_bytes())?;
+        }
+
+        for &i in &order {
+            f.write_all(&blobs[i])?;
+        }
+        f.flush()?;
+        Ok(())
+    }
+
+    // Get the index of a given file name, or `FileNotFound` if not found
+    fn get_index(&self, file: &str) -> Result<u32> {
+        match self.lookup.get(file) {
+            Some(&index) => Ok(index),
+            None => Err(BsaError::FileNotFound(file.to_string())),
+        }
+    }
+}
+
+/// Filesystem-path specific operations, kept separate from the generic
+/// `R: Read + Seek` surface since they need an actual [`File`] to open,
+/// create or `fs::metadata`.
+impl<'a> Tes3Bsa<'a, BufReader<File>> {
+    /// Create a new Tes3Bsa object and open a given file
+    pub fn new(file: &'a str) -> Result<Self> {
+        let mut obj = Self::default();
+        obj.open(file)?;
+        Ok(obj)
+    }
+
+    /// Open a BSA file for reading
+    pub fn open(&mut self, file: &'a str) -> Result<()> {
+        // clear out any existing file data
+        self.filename = file;
+        self.files.clear();
+        self.is_loaded = false;
+        self.lookup.clear();
+        self.reader = None;
+        self.stored_hashes.clear();
+        self.archive_len = 0;
+
+        let mut reader = BufReader::new(File::open(file)?);
+        self.parse_header(&mut reader)?;
+        self.reader = Some(RefCell::new(reader));
+        self.is_loaded = true;
+        Ok(())
+    }
+
+    /// Create a new BSA file, populating it with files from given file
+    /// names. When `compress` is set, every input is deflated and stored
+    /// with its true uncompressed size as a 4-byte LE prefix ahead of the
+    /// deflated data; the directory records the stored (compressed) size.
+    pub fn create(&mut self, file: &'a str, filenames: &[String], compress: bool) -> Result<()> {
+        self.ensure_not_loaded()?;
+        self.filename = file;
+
+        // track bytes written
+        let mut bytes_written: usize = 0;
+        // get file count
+        let filenum = filenames.len() as u32;
+        // Buffer of the bytes that will actually be written to the data
+        // section for each entry, computed up front so compressed sizes
+        // are known before the directory is laid out.
+        let mut buffers: Vec<Vec<u8>> = Vec::with_capacity(filenames.len());
+        let mut buffer_sizes: Vec<usize> = Vec::with_capacity(filenames.len());
+        let mut total_files_size: u32 = 0;
+        for (i, filename) in filenames.iter().enumerate() {
+            let archive_path = filename.to_ascii_lowercase().replace('/', "\\");
+            let raw = fs::read(filename)?;
+            let decompressed_size = raw.len();
+
+            let (stored, compressed_size) = if compress {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&raw)?;
+                let deflated = encoder.finish()?;
+                let mut stored = Vec::with_capacity(4 + deflated.len());
+                stored.extend_from_slice(&(decompressed_size as u32).to_le_bytes());
+                stored.extend_from_slice(&deflated);
+                let compressed_size = stored.len();
+                (stored, compressed_size)
+            } else {
+                (raw, decompressed_size)
+            };
+
+            let fs = FileStruct {
+                compressed_size,
+                decompressed_size,
+                compressed: compress,
+                name: archive_path,
+                offset: total_files_size,
+            };
+            total_files_size += compressed_size as u32;
+
+            self.files.push(fs);
+            buffer_sizes.push(compressed_size);
+            buffers.push(stored);
+        }
+
+        // The directory, filename and hash table sections must be laid out
+        // sorted by name hash, as the format requires for hash-based
+        // lookup. The data section itself is left in input order since
+        // each entry's data-section-relative `offset` is stored with it and
+        // is independent of directory order; only `buffers` (still
+        // input-ordered) is used to write it.
+        let mut dir_order: Vec<usize> = (0..self.files.len()).collect();
+        dir_order.sort_by_key(|&i| calculate_hash(&self.files[i].name));
+        let mut slots: Vec<Option<FileStruct>> = self.files.drain(..).map(Some).collect();
+        self.files = dir_order
+            .iter()
+            .map(|&i| slots[i].take().unwrap())
+            .collect();
+        for (new_index, fs) in self.files.iter().enumerate() {
+            self.lookup.insert(fs.name.to_string(), new_index as u32);
+        }
+
+        // build header
+        let f = File::create(file)?;
+        let mut f = BufWriter::new(f);
+        // write magic header
+        bytes_written += f.write(MAGIC_HEADER)?;
+        // write hashOffset
+        // Offset of the hash table in the file, minus the header size (12)
+        // calculate from 12*numfiles + length of each file name null-terminated
+        let mut hash_offset: u32 = 12 * filenum;
+        for file in &self.files {
+            hash_offset += file.name.chars().count() as u32 + 1;
+        }
+        bytes_written += f.write(&hash_offset.to_le_bytes())?;
+        // write fileCount
+        bytes_written += f.write(&filenum.to_le_bytes())?;
+        check_bytes_written(12, bytes_written)?;
+
+        // write sizes/offsets
+        for file in &self.files {
+            // on-disk file size, with the top bit flagging compression
+            let mut stored_size = file.compressed_size as u32;
+            if file.compressed {
+                stored_size |= COMPRESSED_FLAG;
+            }
+            bytes_written += f.write(&stored_size.to_le_bytes())?;
+            // offset of file in the data section
+            bytes_written += f.write(&file.offset.to_le_bytes())?;
+        }
+
+        // write filename offsets
+        let mut starting_offset: u32 = 0;
+        for file in &self.files {
+            // Relative offset of the filename in the records section
+            bytes_written += f.write(&starting_offset.to_le_bytes())?;
+            let mut filename_length = file.name.chars().count() as u32;
+            filename_length += 1; // null terminator
+            starting_offset += filename_length;
+        }
+        check_bytes_written(12 + 12 * filenum, bytes_written)?;
+
+        // write filesnames, encoded back to Windows-1252 to match what
+        // `parse_header` decodes on read
+        let null_term = [b'\0'];
+        for file in &self.files {
+            let (encoded, _, had_errors) = WINDOWS_1252.encode(&file.name);
+            if had_errors {
+                return Err(BsaError::FilenameEncoding(file.name.clone()));
+            }
+            bytes_written += f.write(&encoded)?;
+            bytes_written += f.write(&null_term)?;
+        }
+
+        // write hash table block
+        for file in &self.files {
+            let hash = calculate_hash(&file.name);
+            bytes_written += f.write(&hash.to_le_bytes())?;
+        }
+
+        // write files, in the original input order matching the offsets
+        // assigned above (independent of the hash-sorted directory order)
+        for (i, stored) in buffers.iter().enumerate() {
+            check_bytes_written(stored.len() as u32, buffer_sizes[i])?;
+
+            // write out the (possibly deflated) file data to the archive
+            f.write_all(stored)?;
+        }
+        f.flush()?;
+        Ok(())
+    }
+}
+
+impl<'a, R: Read + Seek> ArchiveReader for Tes3Bsa<'a, R> {
+    fn list(&self) -> &FileList {
+        self.get_list()
+    }
+
+    fn exists(&self, file: &str) -> bool {
+        Tes3Bsa::exists(self, file)
+    }
+
+    fn get_file(&self, file: &str) -> Result<Vec<u8>> {
+        Tes3Bsa::get_file(self, file)
+    }
+
+    fn get_file_reader(&self, file: &str) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(Tes3Bsa::get_file_reader(self, file)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Hand-assemble a minimal, well-formed TES3 archive holding `entries`,
+    /// each already in its final on-disk form (so a compressed entry's
+    /// `data` must already be the 4-byte length prefix + deflated bytes),
+    /// with a correct hash table, so tests can drive [`Tes3Bsa::read_from`]
+    /// over an in-memory `Cursor` instead of a real file on disk.
+    fn build_archive(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let filenum = entries.len() as u32;
+
+        let name_block: Vec<u8> = entries
+            .iter()
+            .flat_map(|(name, _, _)| {
+                let mut bytes = name.as_bytes().to_vec();
+                bytes.push(0);
+                bytes
+            })
+            .collect();
+        let dirsize = 12 * filenum + name_block.len() as u32;
+
+        let mut size_offset_table = Vec::new();
+        let mut data_section = Vec::new();
+        let mut data_offset = 0u32;
+        for (_, data, compressed) in entries {
+            let mut stored_size = data.len() as u32;
+            if *compressed {
+                stored_size |= COMPRESSED_FLAG;
+            }
+            size_offset_table.extend_from_slice(&stored_size.to_le_bytes());
+            size_offset_table.extend_from_slice(&data_offset.to_le_bytes());
+            data_offset += data.len() as u32;
+            data_section.extend_from_slice(data);
+        }
+        let name_offset_table = vec![0u8; 4 * entries.len()];
+        let hash_table: Vec<u8> = entries
+            .iter()
+            .flat_map(|(name, _, _)| calculate_hash(name).to_le_bytes())
+            .collect();
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC_HEADER);
+        out.extend_from_slice(&dirsize.to_le_bytes());
+        out.extend_from_slice(&filenum.to_le_bytes());
+        out.extend_from_slice(&size_offset_table);
+        out.extend_from_slice(&name_offset_table);
+        out.extend_from_slice(&name_block);
+        out.extend_from_slice(&hash_table);
+        out.extend_from_slice(&data_section);
+        out
+    }
+
+    #[test]
+    fn read_from_parses_an_in_memory_archive() {
+        let bytes = build_archive(&[("meshes\\foo.nif", b"hello", false)]);
+        let bsa = Tes3Bsa::read_from(Cursor::new(bytes)).unwrap();
+
+        assert!(bsa.exists("meshes\\foo.nif"));
+        assert_eq!(bsa.get_file("meshes\\foo.nif").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn compressed_entry_round_trips_through_zlib() {
+        let original = b"some very compressible data data data data data".repeat(4);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&original).unwrap();
+        let deflated = encoder.finish().unwrap();
+
+        let mut stored = Vec::new();
+        stored.extend_from_slice(&(original.len() as u32).to_le_bytes());
+        stored.extend_from_slice(&deflated);
+
+        let bytes = build_archive(&[("textures\\foo.dds", &stored, true)]);
+        let bsa = Tes3Bsa::read_from(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(bsa.get_file("textures\\foo.dds").unwrap(), original);
+    }
+
+    #[test]
+    fn hash_is_case_insensitive_but_not_content_insensitive() {
+        assert_eq!(
+            calculate_hash("Meshes\\Foo.nif"),
+            calculate_hash("meshes\\foo.nif")
+        );
+        assert_ne!(
+            calculate_hash("meshes\\foo.nif"),
+            calculate_hash("meshes\\bar.nif")
+        );
+    }
+
+    #[test]
+    fn verify_flags_only_the_entry_with_a_bad_hash() {
+        let bytes = build_archive(&[
+            ("meshes\\good.nif", b"good", false),
+            ("meshes\\bad.nif", b"bad!", false),
+        ]);
+        let mut bsa = Tes3Bsa::read_from(Cursor::new(bytes)).unwrap();
+        // Corrupt only the second entry's stored hash.
+        bsa.stored_hashes[1] = !bsa.stored_hashes[1];
+
+        let report = bsa.verify().unwrap();
+        let bad_hashes: Vec<&str> = report
+            .into_iter()
+            .filter(|issue| issue.kind == IssueKind::BadHash)
+            .map(|issue| issue.name.as_str())
+            .collect();
+        assert_eq!(bad_hashes, vec!["meshes\\bad.nif"]);
+    }
+
+    #[test]
+    fn add_and_remove_file_round_trip_through_save_as() {
+        let bytes = build_archive(&[("meshes\\keep.nif", b"keep1", false)]);
+        let mut bsa = Tes3Bsa::read_from(Cursor::new(bytes)).unwrap();
+
+        bsa.add_file("meshes\\added.nif", b"added");
+        bsa.add_file("meshes\\removed.nif", b"gone");
+        bsa.remove_file("meshes\\removed.nif");
+        bsa.remove_file("meshes\\does_not_exist.nif");
+
+        let out_path =
+            std::env::temp_dir().join(format!("bsatool_rs_test_{}.bsa", std::process::id()));
+        bsa.save_as(out_path.to_str().unwrap()).unwrap();
+
+        let reopened = Tes3Bsa::new(out_path.to_str().unwrap()).unwrap();
+        assert!(reopened.exists("meshes\\keep.nif"));
+        assert!(reopened.exists("meshes\\added.nif"));
+        assert!(!reopened.exists("meshes\\removed.nif"));
+        assert_eq!(reopened.get_file("meshes\\keep.nif").unwrap(), b"keep1");
+        assert_eq!(reopened.get_file("meshes\\added.nif").unwrap(), b"added");
+
+        std::fs::remove_file(&out_path).unwrap();
+    }
+}
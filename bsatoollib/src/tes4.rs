@@ -0,0 +1,724 @@
+//! The later Bethesda BSA layout used from Oblivion through Skyrim SE: a
+//! 36-byte header followed by a folder-record table, a folder-name block, a
+//! per-folder file-record block and a file-name block, identified by the
+//! `BSA\0` magic. See [`Tes4Bsa`].
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use lz4_flex::frame::{FrameDecoder as Lz4Decoder, FrameEncoder as Lz4Encoder};
+
+use crate::entry::{EntryContents, EntryReader, FileList, FileStruct};
+use crate::error::{BsaError, Result};
+// The real game hash is a different, more involved scheme than this; since
+// nothing in this crate currently validates it on read, `create` reuses
+// TES3's hash purely so entries written by this crate have a stable,
+// internally consistent value.
+use crate::tes3::calculate_hash;
+use crate::ArchiveReader;
+
+/// Magic bytes identifying a TES4-and-later (Oblivion/Skyrim/Fallout) BSA
+pub const MAGIC_HEADER: &[u8] = b"BSA\0";
+
+/// Version tag for Skyrim Special Edition, the first to switch compressed
+/// entries from zlib to LZ4 frames
+const VERSION_SKYRIM_SE: u32 = 0x69;
+
+/// Archive-flags bit: folder names are present in the folder-name block
+const HAS_DIRECTORY_NAMES: u32 = 0x1;
+/// Archive-flags bit: file names are present in the file-name block
+const HAS_FILE_NAMES: u32 = 0x2;
+/// Archive-flags bit: files are compressed unless their own size field says
+/// otherwise
+const COMPRESSED_ARCHIVE: u32 = 0x4;
+/// Top bit of a file record's size field: the entry's compression is the
+/// opposite of the archive-wide default
+const COMPRESSION_FLIP: u32 = 0x8000_0000;
+/// Archive-flags bit: each entry's data region is prefixed with a
+/// length-byte + name string (not null-terminated) ahead of the real data
+const EMBEDDED_FILE_NAMES: u32 = 0x100;
+
+/// A single folder record: its file count. The folder's name is read
+/// separately from the folder-name block
+struct FolderRecord {
+    file_count: u32,
+}
+
+/// Reader/writer for the directory-based BSA layout used by Oblivion,
+/// Skyrim (LE and SE) and Fallout 3/NV. Generic over any `R: Read + Seek`
+/// source for reading, in keeping with [`crate::tes3::Tes3Bsa`]; writing is
+/// filesystem-path-only via [`Tes4Bsa::create`].
+#[derive(Debug)]
+pub struct Tes4Bsa<'a, R = BufReader<File>> {
+    files: FileList,
+    is_loaded: bool,
+    filename: &'a str,
+    lookup: HashMap<String, u32>,
+    reader: Option<RefCell<R>>,
+    /// Format version read from the header (0x67 Oblivion, 0x68 Skyrim LE,
+    /// 0x69 Skyrim SE)
+    version: u32,
+}
+
+impl<'a, R> Default for Tes4Bsa<'a, R> {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            is_loaded: false,
+            filename: "",
+            lookup: HashMap::new(),
+            reader: None,
+            version: 0,
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Tes4Bsa<'a, R> {
+    /// Parse a BSA directory from an arbitrary `Read + Seek` source, keeping
+    /// the reader around so entries can be streamed later.
+    pub fn read_from(mut reader: R) -> Result<Self> {
+        let mut obj = Self::default();
+        obj.parse_header(&mut reader)?;
+        obj.reader = Some(RefCell::new(reader));
+        obj.is_loaded = true;
+        Ok(obj)
+    }
+
+    /// Format version read from the header: 0x67 for Oblivion, 0x68 for
+    /// Skyrim LE, 0x69 for Skyrim SE.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Path this archive was opened or created from, as passed to
+    /// [`Tes4Bsa::new`]/[`Tes4Bsa::open`]/[`Tes4Bsa::create`]. Empty for an
+    /// archive built via [`Tes4Bsa::read_from`].
+    pub fn filename(&self) -> &str {
+        self.filename
+    }
+
+    /// Check whether a given file name exists within the BSA
+    pub fn exists(&self, file: &str) -> bool {
+        self.ensure_loaded().is_ok() && self.get_index(file).is_ok()
+    }
+
+    /// Get the file bytes for a given file name within the BSA. Compressed
+    /// entries are transparently inflated.
+    pub fn get_file(&self, file: &str) -> Result<Vec<u8>> {
+        self.ensure_loaded()?;
+        let i = self.get_index(file)?;
+        let fs = &self.files[i as usize];
+
+        let mut reader = self.reader_for(fs)?;
+        let mut buf = Vec::with_capacity(fs.decompressed_size);
+        reader.read_to_end(&mut buf).map_err(|e| {
+            if fs.compressed {
+                BsaError::Decompress(e.to_string())
+            } else {
+                BsaError::Io(e)
+            }
+        })?;
+        Ok(buf)
+    }
+
+    /// Get a streaming reader over a single file's bytes, without buffering
+    /// the whole entry into memory. Compressed entries are inflated as they
+    /// are read.
+    pub fn get_file_reader(&self, file: &str) -> Result<EntryContents<'_, R>> {
+        self.ensure_loaded()?;
+        let i = self.get_index(file)?;
+        self.reader_for(&self.files[i as usize])
+    }
+
+    /// Get the data for files with the BSA
+    pub fn get_list(&self) -> &FileList {
+        self.ensure_loaded().unwrap();
+        &self.files
+    }
+
+    fn reader_for(&self, fs: &FileStruct) -> Result<EntryContents<'_, R>> {
+        let reader = self.reader.as_ref().ok_or(BsaError::NotOpen)?;
+        let entry = EntryReader::at(reader, fs.offset as u64, fs.compressed_size as u64)?;
+
+        if !fs.compressed {
+            return Ok(EntryContents::Raw(entry));
+        }
+
+        // As in the TES3 layout, a compressed entry is prefixed with a
+        // 4-byte LE uncompressed length ahead of the compressed stream
+        // itself. Skyrim SE (0x69) switched the codec from zlib/DEFLATE to
+        // LZ4 frames; earlier versions (Oblivion, Skyrim LE) stay on zlib.
+        let mut prefixed = entry;
+        let mut len_buf = [0u8; 4];
+        prefixed.read_exact(&mut len_buf)?;
+        if self.version >= VERSION_SKYRIM_SE {
+            Ok(EntryContents::Lz4(Lz4Decoder::new(prefixed)))
+        } else {
+            Ok(EntryContents::Deflate(ZlibDecoder::new(prefixed)))
+        }
+    }
+
+    fn ensure_loaded(&self) -> Result<()> {
+        if !self.is_loaded {
+            return Err(BsaError::NotOpen);
+        }
+        Ok(())
+    }
+
+    fn ensure_not_loaded(&self) -> Result<()> {
+        if self.is_loaded {
+            return Err(BsaError::AlreadyOpen);
+        }
+        Ok(())
+    }
+
+    fn get_index(&self, file: &str) -> Result<u32> {
+        match self.lookup.get(file) {
+            Some(&index) => Ok(index),
+            None => Err(BsaError::FileNotFound(file.to_string())),
+        }
+    }
+
+    /// Read a length-prefixed, null-terminated string: a `u8` byte count
+    /// (including the trailing null) followed by that many bytes.
+    fn read_bstring(file: &mut R) -> Result<String> {
+        let mut len_buf = [0u8; 1];
+        file.read_exact(&mut len_buf)?;
+        let mut buf = vec![0u8; len_buf[0] as usize];
+        file.read_exact(&mut buf)?;
+        if buf.last() == Some(&0) {
+            buf.pop();
+        }
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    /// Parse the archive directory from `file`, populating `self.files` and
+    /// `self.lookup`.
+    fn parse_header(&mut self, file: &mut R) -> Result<()> {
+        self.ensure_not_loaded()?;
+
+        let mut buff = [0u8; 4];
+        file.read_exact(&mut buff)?;
+        if buff != *MAGIC_HEADER {
+            return Err(BsaError::BadHeader);
+        }
+
+        file.read_exact(&mut buff)?;
+        self.version = u32::from_le_bytes(buff);
+
+        // Offset to the folder-record block; always 36 for this header, so
+        // just skip past it rather than seeking.
+        file.read_exact(&mut buff)?;
+
+        file.read_exact(&mut buff)?;
+        let archive_flags = u32::from_le_bytes(buff);
+
+        file.read_exact(&mut buff)?;
+        let folder_count = u32::from_le_bytes(buff);
+
+        file.read_exact(&mut buff)?;
+        let file_count = u32::from_le_bytes(buff);
+
+        // Total byte length of the folder-name block; redundant since each
+        // folder name is itself length-prefixed.
+        file.read_exact(&mut buff)?;
+
+        file.read_exact(&mut buff)?;
+        let total_file_name_length = u32::from_le_bytes(buff);
+
+        // file-flags (u16) + 2 bytes of padding
+        file.read_exact(&mut buff)?;
+
+        let has_folder_names = archive_flags & HAS_DIRECTORY_NAMES != 0;
+        let has_file_names = archive_flags & HAS_FILE_NAMES != 0;
+        let compressed_by_default = archive_flags & COMPRESSED_ARCHIVE != 0;
+        let has_embedded_names = archive_flags & EMBEDDED_FILE_NAMES != 0;
+
+        // Folder records: name hash, file count, offset to the folder's
+        // file-record block. The offset is redundant for us since the
+        // file-record blocks immediately follow the folder-name block in
+        // the same order as the folder records.
+        let mut folders = Vec::with_capacity(folder_count as usize);
+        for _ in 0..folder_count {
+            let mut hash_buf = [0u8; 8];
+            file.read_exact(&mut hash_buf)?;
+            file.read_exact(&mut buff)?;
+            let folder_file_count = u32::from_le_bytes(buff);
+            file.read_exact(&mut buff)?; // offset, unused
+            folders.push(FolderRecord {
+                file_count: folder_file_count,
+            });
+        }
+
+        // Folder-name block and, immediately after each folder's name, that
+        // folder's file-record block.
+        let mut folder_names = Vec::with_capacity(folder_count as usize);
+        let mut file_records: Vec<(u32, u32)> = Vec::with_capacity(file_count as usize);
+        let mut folder_of_file = Vec::with_capacity(file_count as usize);
+        for (folder_index, folder) in folders.iter().enumerate() {
+            let name = if has_folder_names {
+                Self::read_bstring(file)?
+            } else {
+                String::new()
+            };
+            folder_names.push(name);
+
+            for _ in 0..folder.file_count {
+                let mut hash_buf = [0u8; 8];
+                file.read_exact(&mut hash_buf)?;
+                file.read_exact(&mut buff)?;
+                let raw_size = u32::from_le_bytes(buff);
+                file.read_exact(&mut buff)?;
+                let offset = u32::from_le_bytes(buff);
+                file_records.push((raw_size, offset));
+                folder_of_file.push(folder_index);
+            }
+        }
+
+        // File-name block: one null-terminated name per file, in the same
+        // order as the file records were read above.
+        let mut file_names = Vec::with_capacity(file_count as usize);
+        if has_file_names {
+            let mut buf = vec![0u8; total_file_name_length as usize];
+            file.read_exact(&mut buf)?;
+            for chunk in buf.split(|&b| b == 0).take(file_count as usize) {
+                file_names.push(String::from_utf8_lossy(chunk).into_owned());
+            }
+        } else {
+            file_names.resize(file_count as usize, String::new());
+        }
+
+        // When the embedded-file-names flag is set, each entry's data
+        // region is itself prefixed with a length-byte + name string (not
+        // null-terminated) ahead of the real data. Skip over it here so
+        // `offset`/`compressed_size` bracket only the actual bytes,
+        // matching the assumption every other code path below makes.
+        if has_embedded_names {
+            for (raw_size, offset) in file_records.iter_mut() {
+                file.seek(SeekFrom::Start(*offset as u64))?;
+                let mut len_buf = [0u8; 1];
+                file.read_exact(&mut len_buf)?;
+                let prefix_len = 1 + len_buf[0] as u32;
+                *offset += prefix_len;
+                // The stored size counts the whole data block (embedded
+                // name + data), so it over-counts by the same prefix unless
+                // also trimmed here; leave the compression-flip bit alone.
+                *raw_size -= prefix_len;
+            }
+        }
+
+        for (i, (raw_size, offset)) in file_records.into_iter().enumerate() {
+            let flip = raw_size & COMPRESSION_FLIP != 0;
+            let compressed = compressed_by_default ^ flip;
+            let compressed_size = (raw_size & !COMPRESSION_FLIP) as usize;
+
+            let folder_name = &folder_names[folder_of_file[i]];
+            let name = if folder_name.is_empty() {
+                file_names[i].clone()
+            } else {
+                format!("{}\\{}", folder_name, file_names[i])
+            };
+
+            let fs = FileStruct {
+                compressed_size,
+                // Filled in as the true size is only known once we can
+                // peek the entry's 4-byte length prefix below.
+                decompressed_size: compressed_size,
+                compressed,
+                offset,
+                name,
+            };
+            self.lookup.insert(fs.name.clone(), i as u32);
+            self.files.push(fs);
+        }
+
+        // Compressed entries carry a 4-byte LE uncompressed-length prefix
+        // ahead of the compressed stream; peek it now so callers can rely on
+        // `decompressed_size` without touching the reader.
+        for fs in self.files.iter_mut() {
+            if fs.compressed {
+                file.seek(SeekFrom::Start(fs.offset as u64))?;
+                let mut len_buf = [0u8; 4];
+                file.read_exact(&mut len_buf)?;
+                fs.decompressed_size = u32::from_le_bytes(len_buf) as usize;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Filesystem-path specific operations, kept separate from the generic
+/// `R: Read + Seek` surface since they need an actual [`File`] to open.
+impl<'a> Tes4Bsa<'a, BufReader<File>> {
+    /// Create a new Tes4Bsa object and open a given file
+    pub fn new(file: &'a str) -> Result<Self> {
+        let mut obj = Self::default();
+        obj.open(file)?;
+        Ok(obj)
+    }
+
+    /// Open a BSA file for reading
+    pub fn open(&mut self, file: &'a str) -> Result<()> {
+        self.filename = file;
+        self.files.clear();
+        self.is_loaded = false;
+        self.lookup.clear();
+        self.reader = None;
+        self.version = 0;
+
+        let mut reader = BufReader::new(File::open(file)?);
+        self.parse_header(&mut reader)?;
+        self.reader = Some(RefCell::new(reader));
+        self.is_loaded = true;
+        Ok(())
+    }
+
+    /// Create a new directory-format BSA file at `file`, targeting the
+    /// given format `version` (0x67 Oblivion, 0x68 Skyrim LE, 0x69 Skyrim
+    /// SE). `filenames` pairs each on-disk source path with whether that
+    /// file should be stored compressed; compressing all of them sets the
+    /// archive-wide `COMPRESSED_ARCHIVE` flag and compressing none leaves
+    /// it clear, with no per-record exceptions, while a mix sets the flag
+    /// to match the majority (a tie counts as compressed) and flips the
+    /// high bit of the size field for the rest. Skyrim SE entries
+    /// are compressed as LZ4 frames; earlier versions use zlib, matching
+    /// what [`Tes4Bsa::get_file`] expects to decode.
+    pub fn create(
+        &mut self,
+        file: &'a str,
+        filenames: &[(String, bool)],
+        version: u32,
+    ) -> Result<()> {
+        self.ensure_not_loaded()?;
+        self.filename = file;
+        self.version = version;
+
+        let compressed_by_default =
+            filenames.iter().filter(|(_, c)| *c).count() * 2 >= filenames.len();
+
+        // Group source files by archive folder, preserving first-seen
+        // folder order, the way the on-disk folder-record table expects.
+        struct PendingFile {
+            name: String,
+            data: Vec<u8>,
+            compressed: bool,
+        }
+        let mut folder_order: Vec<String> = Vec::new();
+        let mut folder_index: HashMap<String, usize> = HashMap::new();
+        let mut per_folder: Vec<Vec<PendingFile>> = Vec::new();
+
+        for (path, want_compress) in filenames {
+            let archive_path = path.to_ascii_lowercase().replace('/', "\\");
+            let (folder, name) = match archive_path.rsplit_once('\\') {
+                Some((folder, name)) => (folder.to_string(), name.to_string()),
+                None => (String::new(), archive_path.clone()),
+            };
+            let raw = fs::read(path)?;
+
+            let idx = *folder_index.entry(folder.clone()).or_insert_with(|| {
+                folder_order.push(folder);
+                per_folder.push(Vec::new());
+                folder_order.len() - 1
+            });
+            per_folder[idx].push(PendingFile {
+                name,
+                data: raw,
+                compressed: *want_compress,
+            });
+        }
+
+        // Compress each entry (if requested) up front so sizes and offsets
+        // are known before the directory is laid out, mirroring
+        // `Tes3Bsa::create`.
+        struct LaidOutFile {
+            name: String,
+            folder: usize,
+            compressed: bool,
+            stored: Vec<u8>,
+            offset: u32,
+        }
+        let mut laid_out: Vec<LaidOutFile> = Vec::new();
+        let mut running_offset: u32 = 0;
+        for (folder_idx, files) in per_folder.iter().enumerate() {
+            for pending in files {
+                let decompressed_size = pending.data.len();
+                let stored = if pending.compressed {
+                    let mut prefixed = Vec::with_capacity(4 + decompressed_size);
+                    prefixed.extend_from_slice(&(decompressed_size as u32).to_le_bytes());
+                    if version >= VERSION_SKYRIM_SE {
+                        let mut encoder = Lz4Encoder::new(Vec::new());
+                        encoder.write_all(&pending.data)?;
+                        prefixed.extend_from_slice(
+                            &encoder
+                                .finish()
+                                .map_err(|e| BsaError::Decompress(e.to_string()))?,
+                        );
+                    } else {
+                        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                        encoder.write_all(&pending.data)?;
+                        prefixed.extend_from_slice(&encoder.finish()?);
+                    }
+                    prefixed
+                } else {
+                    pending.data.clone()
+                };
+
+                let offset = running_offset;
+                running_offset += stored.len() as u32;
+                laid_out.push(LaidOutFile {
+                    name: pending.name.clone(),
+                    folder: folder_idx,
+                    compressed: pending.compressed,
+                    offset,
+                    stored,
+                });
+            }
+        }
+
+        let folder_count = folder_order.len() as u32;
+        let file_count = laid_out.len() as u32;
+        // Folder/file names are written out verbatim as UTF-8 bytes (see
+        // below), so the block lengths must be byte lengths, not character
+        // counts, or a non-ASCII name desyncs every block that follows.
+        let total_folder_name_length: u32 = folder_order
+            .iter()
+            .map(|name| name.len() as u32 + 2) // length byte + null terminator
+            .sum();
+        let total_file_name_length: u32 = laid_out
+            .iter()
+            .map(|f| f.name.len() as u32 + 1) // null terminator
+            .sum();
+
+        let f = File::create(file)?;
+        let mut f = BufWriter::new(f);
+
+        f.write_all(MAGIC_HEADER)?;
+        f.write_all(&version.to_le_bytes())?;
+        f.write_all(&36u32.to_le_bytes())?; // offset to the folder-record block
+        let mut archive_flags = HAS_DIRECTORY_NAMES | HAS_FILE_NAMES;
+        if compressed_by_default {
+            archive_flags |= COMPRESSED_ARCHIVE;
+        }
+        f.write_all(&archive_flags.to_le_bytes())?;
+        f.write_all(&folder_count.to_le_bytes())?;
+        f.write_all(&file_count.to_le_bytes())?;
+        f.write_all(&total_folder_name_length.to_le_bytes())?;
+        f.write_all(&total_file_name_length.to_le_bytes())?;
+        f.write_all(&0u16.to_le_bytes())?; // file-flags
+        f.write_all(&0u16.to_le_bytes())?; // padding
+
+        // Folder records: name hash, file count, offset of the folder's
+        // file-record block, written as the running byte count of the file
+        // records before it (starting at 0, not an offset from the start of
+        // the file).
+        let mut folder_block_offset = 0u32;
+        for folder in &folder_order {
+            let count = per_folder[folder_index[folder]].len() as u32;
+            f.write_all(&calculate_hash(&(folder.clone() + "\0")).to_le_bytes())?;
+            f.write_all(&count.to_le_bytes())?;
+            f.write_all(&folder_block_offset.to_le_bytes())?;
+            folder_block_offset += count * 16;
+        }
+
+        // Absolute offset of the data section: header, folder records,
+        // folder names interleaved with file records, then the file-name
+        // block. Entry offsets on disk are absolute from the start of the
+        // file, unlike `entry.offset` above which is data-section-relative.
+        let data_offset = 36
+            + folder_count * 16
+            + total_folder_name_length
+            + file_count * 16
+            + total_file_name_length;
+
+        // Folder-name block interleaved with each folder's file-record
+        // block, in folder order.
+        for folder in &folder_order {
+            let mut encoded = folder.clone();
+            encoded.push('\0');
+            f.write_all(&[encoded.len() as u8])?;
+            f.write_all(encoded.as_bytes())?;
+
+            for entry in laid_out
+                .iter()
+                .filter(|e| folder_order[e.folder] == *folder)
+            {
+                let mut size = entry.stored.len() as u32;
+                if entry.compressed != compressed_by_default {
+                    size |= COMPRESSION_FLIP;
+                }
+                f.write_all(&calculate_hash(&(entry.name.clone() + "\0")).to_le_bytes())?;
+                f.write_all(&size.to_le_bytes())?;
+                f.write_all(&(entry.offset + data_offset).to_le_bytes())?;
+            }
+        }
+
+        // File-name block, in the same order as the file records above.
+        for entry in &laid_out {
+            f.write_all(entry.name.as_bytes())?;
+            f.write_all(&[0u8])?;
+        }
+
+        // Data section, laid out contiguously in the same order the
+        // offsets above were assigned.
+        for entry in &laid_out {
+            f.write_all(&entry.stored)?;
+        }
+        f.flush()?;
+        Ok(())
+    }
+}
+
+impl<'a, R: Read + Seek> ArchiveReader for Tes4Bsa<'a, R> {
+    fn list(&self) -> &FileList {
+        self.get_list()
+    }
+
+    fn exists(&self, file: &str) -> bool {
+        Tes4Bsa::exists(self, file)
+    }
+
+    fn get_file(&self, file: &str) -> Result<Vec<u8>> {
+        Tes4Bsa::get_file(self, file)
+    }
+
+    fn get_file_reader(&self, file: &str) -> Result<Box<dyn Read + '_>> {
+        Ok(Box::new(Tes4Bsa::get_file_reader(self, file)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Hand-assemble a minimal, single-folder, single-file TES4 archive, so
+    /// tests can drive [`Tes4Bsa::read_from`] over an in-memory `Cursor`
+    /// instead of a real file on disk. `data` is the entry's final on-disk
+    /// bytes (e.g. already including the embedded-name prefix, if any).
+    fn build_archive(
+        archive_flags: u32,
+        version: u32,
+        folder: &str,
+        name: &str,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let folder_name_block = {
+            let mut bstring = folder.as_bytes().to_vec();
+            bstring.push(0);
+            let mut out = vec![bstring.len() as u8];
+            out.extend_from_slice(&bstring);
+            out
+        };
+        let file_name_block = {
+            let mut out = name.as_bytes().to_vec();
+            out.push(0);
+            out
+        };
+
+        let data_offset = 36
+            + 16 // one folder record
+            + folder_name_block.len() as u32
+            + 16 // one file record
+            + file_name_block.len() as u32;
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC_HEADER);
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&36u32.to_le_bytes());
+        out.extend_from_slice(&archive_flags.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes()); // folder_count
+        out.extend_from_slice(&1u32.to_le_bytes()); // file_count
+        out.extend_from_slice(&(folder_name_block.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(file_name_block.len() as u32).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // file-flags
+        out.extend_from_slice(&0u16.to_le_bytes()); // padding
+
+        // folder record: hash (unused by the reader), file count, offset (unused)
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+
+        out.extend_from_slice(&folder_name_block);
+
+        // file record: hash (unused), raw size, absolute offset
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&data_offset.to_le_bytes());
+
+        out.extend_from_slice(&file_name_block);
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[test]
+    fn read_from_parses_an_in_memory_archive() {
+        let flags = HAS_DIRECTORY_NAMES | HAS_FILE_NAMES;
+        let bytes = build_archive(flags, 0x67, "meshes", "foo.nif", b"hello!");
+        let bsa = Tes4Bsa::read_from(Cursor::new(bytes)).unwrap();
+
+        assert!(bsa.exists("meshes\\foo.nif"));
+        assert_eq!(bsa.get_file("meshes\\foo.nif").unwrap(), b"hello!");
+    }
+
+    #[test]
+    fn embedded_file_names_are_stripped_from_the_data_and_the_size() {
+        let flags = HAS_DIRECTORY_NAMES | HAS_FILE_NAMES | EMBEDDED_FILE_NAMES;
+        let payload = b"hello!";
+        let embedded_name = b"foo.nif";
+        let mut data = Vec::new();
+        data.push(embedded_name.len() as u8);
+        data.extend_from_slice(embedded_name);
+        data.extend_from_slice(payload);
+
+        let bytes = build_archive(flags, 0x67, "meshes", "foo.nif", &data);
+        let bsa = Tes4Bsa::read_from(Cursor::new(bytes)).unwrap();
+
+        // Regression test for the raw_size-vs-offset desync: both must be
+        // trimmed by the embedded name's length, or this either reads
+        // garbage from the name bytes or trails into the next entry.
+        assert_eq!(bsa.get_file("meshes\\foo.nif").unwrap(), payload);
+    }
+
+    #[test]
+    fn compressed_entry_round_trips_through_zlib() {
+        let original = b"some very compressible data data data data data".repeat(4);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&original).unwrap();
+        let deflated = encoder.finish().unwrap();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&(original.len() as u32).to_le_bytes());
+        data.extend_from_slice(&deflated);
+
+        let flags = HAS_DIRECTORY_NAMES | HAS_FILE_NAMES | COMPRESSED_ARCHIVE;
+        let bytes = build_archive(flags, 0x67, "textures", "foo.dds", &data);
+        let bsa = Tes4Bsa::read_from(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(bsa.get_file("textures\\foo.dds").unwrap(), original);
+    }
+
+    #[test]
+    fn compressed_entry_round_trips_through_lz4_on_skyrim_se() {
+        let original = b"some very compressible data data data data data".repeat(4);
+        let mut encoder = Lz4Encoder::new(Vec::new());
+        encoder.write_all(&original).unwrap();
+        let framed = encoder.finish().unwrap();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&(original.len() as u32).to_le_bytes());
+        data.extend_from_slice(&framed);
+
+        let flags = HAS_DIRECTORY_NAMES | HAS_FILE_NAMES | COMPRESSED_ARCHIVE;
+        let bytes = build_archive(flags, VERSION_SKYRIM_SE, "textures", "foo.dds", &data);
+        let bsa = Tes4Bsa::read_from(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(bsa.get_file("textures\\foo.dds").unwrap(), original);
+    }
+}
@@ -46,31 +46,51 @@ enum Commands {
         #[arg(short, long, default_value_t = String::from("."), value_hint = clap::ValueHint::DirPath)]
         output: String,
     },
+    /// Recompute name hashes and check entry offsets for the given BSA file
+    Verify,
+    /// Mount the given BSA file as a read-only filesystem via FUSE
+    Mount {
+        /// Directory to mount the archive on
+        mountpoint: String,
+    },
     /// Create a new BSA file with given files for archiving
     Create {
         /// Files to add to BSA
         #[arg(short, long)]
         files: Vec<String>,
+        /// Deflate each input file and store it compressed
+        #[arg(short, long, default_value_t = false)]
+        compress: bool,
     },
 }
 
 fn list(bsa: bsa::BSAFile, long_format: bool) {
-    let files = bsa.get_list();
+    let files = bsa.list();
 
     if long_format {
+        let mut builder = Builder::default();
+        builder.set_header(["name", "size", "compressed", "decompressed", "offset"]);
         for file in files {
-            println!("{}", file.name)
+            builder.push_record([
+                file.name.to_string(),
+                file.compressed_size.to_string(),
+                file.compressed.to_string(),
+                file.decompressed_size.to_string(),
+                format!("0x{:x}", file.offset),
+            ]);
         }
+        let mut table = builder.build();
+        table.with(Style::modern());
+        println!("{}", table);
         return;
     }
 
-    // longformat
     let mut builder = Builder::default();
     builder.set_header(["name", "size", "offset"]);
     for file in files {
         builder.push_record([
             file.name.to_string(),
-            file.file_size.to_string(),
+            file.decompressed_size.to_string(),
             format!("0x{:x}", file.offset),
         ]);
     }
@@ -112,10 +132,7 @@ fn extract(
             );
         }
 
-        // Get a buffer for the file to extract
-        let data = bsa.get_file(&archive_path)?;
-
-        // Write the file to disk
+        // Stream the file straight to disk instead of buffering it whole
         println!(
             "Extracting {} to {}",
             extract_file,
@@ -123,7 +140,7 @@ fn extract(
         );
         let f = File::create(target).expect("Unable to create file");
         let mut f = BufWriter::new(f);
-        f.write_all(&data)?;
+        bsa.extract_to(&archive_path, &mut f)?;
         f.flush()?;
     }
     Ok(())
@@ -131,7 +148,7 @@ fn extract(
 
 fn extract_all(bsa: bsa::BSAFile, out_dir: &String) -> Result<()> {
     // Get the list of files present in the archive
-    let list = bsa.get_list();
+    let list = bsa.list();
     let pb = ProgressBar::new(list.len() as u64);
 
     for file in list {
@@ -151,13 +168,10 @@ fn extract_all(bsa: bsa::BSAFile, out_dir: &String) -> Result<()> {
             );
         }
 
-        // Get a buffer for the file to extract
-        let data = bsa.get_file(&file.name)?;
-
-        // Write the file to disk
+        // Stream the file straight to disk instead of buffering it whole
         let f = File::create(target).expect("Unable to create file");
         let mut f = BufWriter::new(f);
-        f.write_all(&data)?;
+        bsa.extract_to(&file.name, &mut f)?;
         f.flush()?;
     }
     pb.finish_with_message("done");
@@ -187,8 +201,29 @@ fn main() {
             bsa.open(&filename).unwrap();
             extract_all(bsa, output).unwrap();
         }
-        Commands::Create { files } => {
-            bsa.create(&filename, files).unwrap();
+        Commands::Verify => {
+            // Verification is a TES3-specific operation (it checks the
+            // archive's hash table), so it goes straight through the
+            // concrete backend rather than the format-agnostic dispatcher.
+            let tes3 = bsa::tes3::Tes3Bsa::new(&filename).unwrap();
+            let issues = tes3.verify().unwrap();
+            if issues.is_empty() {
+                println!("OK: {} is consistent", filename);
+            } else {
+                for issue in &issues {
+                    println!("{}: {}", issue.name, issue.message);
+                }
+                std::process::exit(1);
+            }
+        }
+        Commands::Mount { mountpoint } => {
+            bsa.open(&filename).unwrap();
+            let fs = bsa::mount::BsaFuse::new(bsa);
+            fuser::mount2(fs, &mountpoint, &[]).unwrap();
+        }
+        Commands::Create { files, compress } => {
+            let mut tes3 = bsa::tes3::Tes3Bsa::default();
+            tes3.create(&filename, files, *compress).unwrap();
         }
     }
 }